@@ -0,0 +1,10 @@
+// Known Blaze component/command space. Add new commands here rather than matching
+// on raw `LabeledTdf` lists in `handle_client`.
+blaze_components! {
+    component Authentication(0x1) {
+        command Login(0x1) {
+            email: String = "MAIL",
+            password: String = "PASS",
+        }
+    }
+}
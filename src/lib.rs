@@ -0,0 +1,16 @@
+//! Core library surface for the `rme3` Blaze server: wire protocol types (`tdf`), the
+//! frame codec (`packet`), the generated component/command router (`components`) and
+//! the transport abstraction (`transport`). Split out from the binary so these are a
+//! real, reusable dependency surface rather than items only reachable from `main`.
+
+// Lets tdf_derive's generated code refer to this crate as `rme3::...` even when the
+// derive is used inside rme3 itself, not just by external consumers depending on it
+// by that name.
+extern crate self as rme3;
+
+#[macro_use]
+pub mod macros;
+pub mod components;
+pub mod packet;
+pub mod tdf;
+pub mod transport;
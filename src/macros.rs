@@ -0,0 +1,71 @@
+/// Declares the Blaze component/command space in one place and generates a
+/// `DecodedPacket` enum, a `packet_by_id` dispatcher and a typed request struct
+/// per command whose fields are pulled out of the packet's `LabeledTdf` list.
+///
+/// ```ignore
+/// blaze_components! {
+///     component Authentication(0x1) {
+///         command Login(0x1) {
+///             email: String = "MAIL",
+///             password: String = "PASS",
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! blaze_components {
+    (
+        $(
+            component $component_name:ident ($component_id:literal) {
+                $(
+                    command $command_name:ident ($command_id:literal) {
+                        $( $field_name:ident : $field_type:ty = $field_tag:literal ),* $(,)?
+                    }
+                )*
+            }
+        )*
+    ) => {
+        #[derive(Debug)]
+        pub enum DecodedPacket {
+            $( $( $command_name($command_name), )* )*
+            Unknown(u16, u16),
+        }
+
+        $(
+            $(
+                #[derive(Debug)]
+                pub struct $command_name {
+                    $( pub $field_name: $field_type, )*
+                }
+
+                impl $command_name {
+                    fn decode(values: &[$crate::tdf::LabeledTdf]) -> std::io::Result<Self> {
+                        Ok(Self {
+                            $( $field_name: $crate::tdf::find_field(values, $field_tag)?, )*
+                        })
+                    }
+                }
+            )*
+        )*
+
+        /// Dispatches a packet's component/command pair to the matching typed request
+        /// struct, decoding its fields from the content cursor.
+        pub fn packet_by_id(component: u16, command: u16, cursor: &mut std::io::Cursor<Vec<u8>>) -> std::io::Result<DecodedPacket> {
+            use $crate::tdf::Readable;
+
+            let length = cursor.get_ref().len() as u64;
+            let mut values = Vec::new();
+            while cursor.position() < length {
+                values.push($crate::tdf::LabeledTdf::read(cursor)?);
+            }
+            match (component, command) {
+                $(
+                    $(
+                        ($component_id, $command_id) => Ok(DecodedPacket::$command_name($command_name::decode(&values)?)),
+                    )*
+                )*
+                _ => Ok(DecodedPacket::Unknown(component, command)),
+            }
+        }
+    };
+}
@@ -1,47 +1,75 @@
 use std::io;
 use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
 
-use crate::packet::{read_packet, read_packet_contents};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::accept_async;
 
-mod tdf;
-mod packet;
+use rme3::components::packet_by_id;
+use rme3::transport::{TcpTransport, Transport, WsTransport};
 
-const HOST: &str = "127.0.0.1:14219";
+/// Bind address as a URL: `tcp://` (the default) speaks raw Blaze frames directly over
+/// TCP, `ws://` accepts the same frames tunnelled through a WebSocket upgrade for clients
+/// behind networks that only allow outbound web traffic. `wss://` is not implemented yet —
+/// there is no TLS acceptor wired up, so terminate TLS in front of this process (e.g. a
+/// reverse proxy) and bind `ws://` behind it rather than trusting this binary with it.
+const BIND: &str = "tcp://127.0.0.1:14219";
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    let listener = TcpListener::bind(HOST).await?;
-    println!("Server listening on {0}", HOST);
+    let (scheme, host) = BIND.split_once("://").unwrap_or(("tcp", BIND));
+    if scheme == "wss" {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "wss:// requires a TLS acceptor, which this server does not implement; \
+             terminate TLS in a reverse proxy and bind ws:// behind it instead",
+        ));
+    }
+    let listener = TcpListener::bind(host).await?;
+    println!("Server listening on {0} ({1})", host, scheme);
     loop {
         if let Ok((stream, addr)) = listener.accept().await {
+            let scheme = scheme.to_string();
             tokio::spawn(async move {
-                handle_client(stream, addr).await
+                match accept_transport(&scheme, stream).await {
+                    Ok(transport) => handle_client(transport, addr).await,
+                    Err(err) => eprintln!("Failed to set up connection from {0}: {1:?}", addr, err),
+                }
             });
         }
     }
 }
 
+/// Performs whatever handshake `scheme` requires and returns the resulting transport.
+async fn accept_transport(scheme: &str, stream: TcpStream) -> io::Result<Box<dyn Transport>> {
+    match scheme {
+        "ws" => {
+            let ws = accept_async(stream).await
+                .map_err(io::Error::other)?;
+            Ok(Box::new(WsTransport::new(ws)))
+        }
+        _ => Ok(Box::new(TcpTransport::new(stream))),
+    }
+}
 
-async fn handle_client(mut stream: TcpStream, addr: SocketAddr) {
+async fn handle_client(mut transport: Box<dyn Transport>, addr: SocketAddr) {
     println!("New client connected at address {0}\n", addr);
-    'ga: loop {
-        let packet = read_packet(&mut stream).await;
-        match packet {
-            Ok(packet) => {
+    loop {
+        match transport.read_packet().await {
+            Ok(Some(packet)) => {
                 println!("{:?}", packet);
-                match read_packet_contents(&packet) {
-                    Ok(content) => {
-                        println!("{:?}", content);
+                match packet_by_id(packet.component(), packet.command(), &mut packet.content_cursor()) {
+                    Ok(decoded) => {
+                        println!("{:?}", decoded);
                     }
                     Err(err) => {
                         eprintln!("{:?}", err);
                     }
                 }
             }
+            Ok(None) => break,
             Err(err) => {
                 eprintln!("{:?}", err);
-                break 'ga;
+                break;
             }
         }
     }
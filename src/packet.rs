@@ -1,10 +1,10 @@
 use std::io;
-use std::io::{Cursor, Read};
+use std::io::Cursor;
 
-use tokio::io::{AsyncReadExt};
-use tokio::net::TcpStream;
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
 
-use crate::tdf::{LabeledTdf, Readable};
+use crate::tdf::{LabeledTdf, Writeable};
 
 #[derive(Debug)]
 pub struct Packet {
@@ -16,34 +16,114 @@ pub struct Packet {
     content: Vec<u8>,
 }
 
-pub async fn read_packet(r: &mut TcpStream) -> io::Result<Packet> {
-    let length = r.read_u16().await? as usize;
-    let component = r.read_u16().await?;
-    let command = r.read_u16().await?;
-    let error = r.read_u16().await?;
-    let qtype = r.read_u16().await?;
-    let id = r.read_u16().await?;
-    let ext_length = if (qtype & 0x10) != 0 { r.read_u16().await? } else { 0u16 };
-    let content_length = length + ((ext_length as usize) << 16);
-    let mut content = vec![0u8; content_length];
-    r.read_exact(&mut content).await?;
-    Ok(Packet {
-        component,
-        command,
-        error,
-        qtype,
-        id,
-        content,
-    })
+impl Packet {
+    /// Builds a new packet from a content body of labeled TDF values, serializing
+    /// them up front so the qtype/ext_length split only has to be computed once
+    /// when the packet is written out.
+    pub fn new(component: u16, command: u16, id: u16, qtype: u16, content: Vec<LabeledTdf>) -> io::Result<Packet> {
+        let mut buffer = Vec::new();
+        for value in &content {
+            value.write(&mut buffer)?;
+        }
+        Ok(Packet {
+            component,
+            command,
+            error: 0,
+            qtype,
+            id,
+            content: buffer,
+        })
+    }
+
+    pub fn component(&self) -> u16 {
+        self.component
+    }
+
+    pub fn command(&self) -> u16 {
+        self.command
+    }
+
+    /// A fresh cursor over the packet's content, for consumers (e.g. `packet_by_id`)
+    /// that need to walk its `LabeledTdf` list.
+    pub fn content_cursor(&self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.content.clone())
+    }
 }
 
-pub fn read_packet_contents(packet: &Packet) -> io::Result<Vec<LabeledTdf>> {
-    let raw_content = packet.content.clone();
-    let length = raw_content.len();
-    let mut cursor = Cursor::new(raw_content);
-    let mut content = Vec::new();
-    while cursor.position() < length as u64 {
-        content.push(LabeledTdf::read(&mut cursor)?);
+/// The fixed portion of a Blaze frame header: `length`, `component`, `command`,
+/// `error`, `qtype` and `id`, each a `u16`.
+const HEADER_LENGTH: usize = 12;
+
+/// `tokio_util` codec for Blaze frames, used to drive a `Framed<TcpStream, BlazePacketCodec>`
+/// as a `Stream`/`Sink` of `Packet`s instead of hand-rolling the read loop.
+#[derive(Default)]
+pub struct BlazePacketCodec;
+
+impl Decoder for BlazePacketCodec {
+    type Item = Packet;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Packet>> {
+        if src.len() < HEADER_LENGTH {
+            return Ok(None);
+        }
+        let length = u16::from_be_bytes([src[0], src[1]]) as usize;
+        let component = u16::from_be_bytes([src[2], src[3]]);
+        let command = u16::from_be_bytes([src[4], src[5]]);
+        let error = u16::from_be_bytes([src[6], src[7]]);
+        let qtype = u16::from_be_bytes([src[8], src[9]]);
+        let id = u16::from_be_bytes([src[10], src[11]]);
+        let has_ext = (qtype & 0x10) != 0;
+        let header_length = if has_ext { HEADER_LENGTH + 2 } else { HEADER_LENGTH };
+        if src.len() < header_length {
+            return Ok(None);
+        }
+        let ext_length = if has_ext { u16::from_be_bytes([src[12], src[13]]) as usize } else { 0 };
+        let content_length = length + (ext_length << 16);
+        let frame_length = header_length + content_length;
+        if src.len() < frame_length {
+            src.reserve(frame_length - src.len());
+            return Ok(None);
+        }
+        let mut frame = src.split_to(frame_length);
+        frame.advance(header_length);
+        Ok(Some(Packet {
+            component,
+            command,
+            error,
+            qtype,
+            id,
+            content: frame.to_vec(),
+        }))
     }
-    return Ok(content);
 }
+
+impl Encoder<Packet> for BlazePacketCodec {
+    type Error = io::Error;
+
+    /// Mirrors the header layout `decode` understands: the low 16 bits of the content
+    /// length go in `length`, and if the content is larger than `0xFFFF` the `0x10`
+    /// bit is set on `qtype` and the high bits are written as `ext_length`.
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> io::Result<()> {
+        let content_length = packet.content.len();
+        let length = (content_length & 0xFFFF) as u16;
+        let mut qtype = packet.qtype;
+        if content_length > 0xFFFF {
+            qtype |= 0x10;
+        }
+        dst.reserve(HEADER_LENGTH + 2 + content_length);
+        dst.put_u16(length);
+        dst.put_u16(packet.component);
+        dst.put_u16(packet.command);
+        dst.put_u16(packet.error);
+        dst.put_u16(qtype);
+        dst.put_u16(packet.id);
+        if (qtype & 0x10) != 0 {
+            let ext_length = (content_length >> 16) as u16;
+            dst.put_u16(ext_length);
+        }
+        dst.put_slice(&packet.content);
+        Ok(())
+    }
+}
+
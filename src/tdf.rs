@@ -1,3 +1,4 @@
+use std::fmt;
 use std::io;
 use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 
@@ -96,15 +97,18 @@ pub trait Readable: Send + Sync {
 
 impl Writeable for VarInt {
     fn write<W: Write>(&self, o: &mut W) -> io::Result<()> {
+        // Thrift compact-protocol zigzag: map the signed value to an unsigned magnitude
+        // before splitting it into 7-bit groups, so negative values round-trip.
         let value = self.0;
-        if value < 0x40 {
-            o.write_u8((value & 0xFF) as u8)?
+        let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        if zigzag < 0x40 {
+            o.write_u8(zigzag as u8)?
         } else {
-            let mut curr_byte = ((value & 0x3F) as u8) | 0x80;
+            let mut curr_byte = ((zigzag & 0x3F) as u8) | 0x80;
             o.write_u8(curr_byte)?;
-            let mut curr_shift = value >> 6;
+            let mut curr_shift = zigzag >> 6;
             while curr_shift >= 0x80 {
-                curr_byte = ((curr_shift & 0x7F) | 0x80) as u8;
+                curr_byte = ((curr_shift & 0x7F) as u8) | 0x80;
                 curr_shift >>= 7;
                 o.write_u8(curr_byte)?;
             }
@@ -118,16 +122,18 @@ impl Readable for VarInt {
     fn read<R: Read + Seek>(r: &mut R) -> io::Result<VarInt> {
         let first = r.read_u8()?;
         let mut shift = 6;
-        let mut result = (first & 0x3F) as i64;
+        let mut zigzag = (first & 0x3F) as u64;
         if first >= 0x80 {
             let mut byte: u8;
             loop {
                 byte = r.read_u8()?;
-                result |= ((byte & 0x7F) as i64) << shift;
+                zigzag |= ((byte & 0x7F) as u64) << shift;
+                shift += 7;
                 if byte < 0x80 { break; }
             };
         }
-        return Ok(VarInt(result));
+        let value = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+        Ok(VarInt(value))
     }
 }
 
@@ -135,9 +141,7 @@ impl Writeable for String {
     fn write<W: Write>(&self, o: &mut W) -> io::Result<()> {
         let mut value = self.clone();
         let null_char = char::from(0);
-        if value.len() < 1 {
-            value.push(null_char)
-        } else if value.chars().last().unwrap() != null_char {
+        if !value.ends_with(null_char) {
             value.push(null_char)
         }
         VarInt::from(self.len()).write(o)?;
@@ -179,11 +183,11 @@ impl LabeledTdf {
         res[2] |= (buff[3] & 0x40) >> 1;
         res[2] |= buff[3] & 0x1F;
 
-        return res;
+        res
     }
 
     fn tag_to_label(tag: u32) -> String {
-        let mut buff: [u8; 4] = tag.to_be_bytes();
+        let buff: [u8; 4] = tag.to_be_bytes();
         let mut res = [0u8; 4];
         res[0] |= (buff[0] & 0x80) >> 1;
         res[0] |= (buff[0] & 0x40) >> 2;
@@ -202,9 +206,9 @@ impl LabeledTdf {
         res[3] |= (buff[2] & 0x20) << 1;
         res[3] |= buff[2] & 0x1F;
 
-        return res.iter()
+        res.iter()
             .filter_map(|v| if *v == 0 { None } else { Some(char::from(*v)) })
-            .collect::<String>();
+            .collect::<String>()
     }
 }
 
@@ -220,17 +224,27 @@ impl Writeable for LabeledTdf {
     }
 }
 
-impl Readable for LabeledTdf {
-    fn read<R: Read + Seek>(r: &mut R) -> io::Result<Self> where Self: Sized {
-        let head = r.read_u32::<BigEndian>()?;
+impl LabeledTdf {
+    /// Structured read used internally so decode failures carry a breadcrumb path
+    /// instead of collapsing into a bare `io::Error` at every nesting level. The
+    /// public `Readable` impl below converts to `io::Error` only once, at the top.
+    fn read_tdf<R: Read + Seek>(r: &mut R) -> TdfResult<Self> {
+        let offset = io_to_tdf(r.stream_position(), 0)?;
+        let head = io_to_tdf(r.read_u32::<BigEndian>(), offset)?;
         let tag = head & 0xFFFFFF00;
         let label = LabeledTdf::tag_to_label(tag);
         let tdf_type = TdfType::from((head & 0xFF) as u8);
-        let tdf = Tdf::read(r, &tdf_type)?;
+        let tdf = Tdf::read(r, &tdf_type).map_err(|err| err.within(&label))?;
         Ok(LabeledTdf(label, tdf_type, tdf))
     }
 }
 
+impl Readable for LabeledTdf {
+    fn read<R: Read + Seek>(r: &mut R) -> io::Result<Self> where Self: Sized {
+        LabeledTdf::read_tdf(r).map_err(Into::into)
+    }
+}
+
 impl Writeable for Tdf {
     fn write<W: Write>(&self, o: &mut W) -> io::Result<()> {
         match self {
@@ -258,7 +272,7 @@ impl Writeable for Tdf {
                 o.write_u8(key_type.value())?;
                 o.write_u8(value_type.value())?;
                 let length = keys.len();
-                for i in 0..(length - 1) {
+                for i in 0..length {
                     let key = keys.get(i).unwrap();
                     let value = values.get(i).unwrap();
                     key.write(o)?;
@@ -294,112 +308,302 @@ impl Writeable for Tdf {
     }
 }
 
+/// Extracts a typed Rust value out of a decoded `Tdf`, used by the generated
+/// `packet_by_id` dispatcher to turn a group's `LabeledTdf` list into typed request structs.
+pub trait FromTdf: Sized {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self>;
+}
+
+impl FromTdf for String {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self> {
+        match tdf {
+            Tdf::String(value) => Ok(value.clone()),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "expected a string tdf value")),
+        }
+    }
+}
+
+impl FromTdf for VarInt {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self> {
+        match tdf {
+            Tdf::VarInt(value) => Ok(value.clone()),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "expected a varint tdf value")),
+        }
+    }
+}
+
+impl FromTdf for f32 {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self> {
+        match tdf {
+            Tdf::Float(value) => Ok(*value),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "expected a float tdf value")),
+        }
+    }
+}
+
+impl<T: FromTdf> FromTdf for Vec<T> {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self> {
+        match tdf {
+            Tdf::List(_, values) => values.iter().map(T::from_tdf).collect(),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "expected a list tdf value")),
+        }
+    }
+}
+
+impl<K: FromTdf + Eq + std::hash::Hash, V: FromTdf> FromTdf for std::collections::HashMap<K, V> {
+    fn from_tdf(tdf: &Tdf) -> io::Result<Self> {
+        match tdf {
+            Tdf::Map(_, _, keys, values) => keys.iter().zip(values.iter())
+                .map(|(key, value)| Ok((K::from_tdf(key)?, V::from_tdf(value)?)))
+                .collect(),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "expected a map tdf value")),
+        }
+    }
+}
+
+/// Converts a typed Rust value into a `Tdf`, the inverse of `FromTdf`. Used by
+/// `#[derive(TdfSerialize)]` to turn a struct's fields into `LabeledTdf` entries.
+pub trait IntoTdf {
+    // Takes `&self` rather than `self`: callers (the derive, `Vec`/`HashMap` impls) only
+    // ever hold a borrowed field, never an owned value to consume.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_tdf(&self) -> Tdf;
+
+    /// The `TdfType` this value serializes as, used to fill in a `LabeledTdf`'s type
+    /// byte without the derive having to guess it from the field's Rust type.
+    fn tdf_type() -> TdfType where Self: Sized;
+}
+
+impl IntoTdf for String {
+    fn into_tdf(&self) -> Tdf {
+        Tdf::String(self.clone())
+    }
+
+    fn tdf_type() -> TdfType {
+        TdfType::String
+    }
+}
+
+impl IntoTdf for VarInt {
+    fn into_tdf(&self) -> Tdf {
+        Tdf::VarInt(self.clone())
+    }
+
+    fn tdf_type() -> TdfType {
+        TdfType::VarInt
+    }
+}
+
+impl IntoTdf for f32 {
+    fn into_tdf(&self) -> Tdf {
+        Tdf::Float(*self)
+    }
+
+    fn tdf_type() -> TdfType {
+        TdfType::Float
+    }
+}
+
+impl<T: IntoTdf> IntoTdf for Vec<T> {
+    fn into_tdf(&self) -> Tdf {
+        Tdf::List(T::tdf_type(), self.iter().map(IntoTdf::into_tdf).collect())
+    }
+
+    fn tdf_type() -> TdfType {
+        TdfType::List
+    }
+}
+
+impl<K: IntoTdf, V: IntoTdf> IntoTdf for std::collections::HashMap<K, V> {
+    fn into_tdf(&self) -> Tdf {
+        let (keys, values) = self.iter()
+            .map(|(key, value)| (key.into_tdf(), value.into_tdf()))
+            .unzip();
+        Tdf::Map(K::tdf_type(), V::tdf_type(), keys, values)
+    }
+
+    fn tdf_type() -> TdfType {
+        TdfType::Map
+    }
+}
+
+/// Looks up a field by its 4-character tag within a group's labeled values and
+/// converts it to `T`. Trailing spaces in `label` are ignored, since a padded tag such as
+/// `"PID "` decodes without them.
+pub fn find_field<T: FromTdf>(values: &[LabeledTdf], label: &str) -> io::Result<T> {
+    let label = label.trim_end_matches(' ');
+    values.iter()
+        .find(|value| value.0 == label)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("missing field '{}'", label)))
+        .and_then(|value| T::from_tdf(&value.2))
+}
+
 type TdfResult<R> = Result<R, TdfError>;
 
-enum TdfError {
-    MissingLabel,
-    NotGroup,
-    InvalidType,
+#[derive(Debug)]
+enum TdfErrorKind {
+    Io(String),
+}
+
+/// A TDF decode failure, carrying the breadcrumb of tag labels leading to the field
+/// that failed (e.g. `AUTH.PDTL.MAIL`) and the cursor offset at which the innermost
+/// failing value (or field header) started, so a malformed field deep in a nested group gives more than an opaque `UnexpectedEof`.
+#[derive(Debug)]
+pub struct TdfError {
+    path: Vec<String>,
+    offset: u64,
+    kind: TdfErrorKind,
+}
+
+impl TdfError {
+    fn new(kind: TdfErrorKind, offset: u64) -> Self {
+        TdfError { path: Vec::new(), offset, kind }
+    }
+
+    /// Prepends a tag label as the error unwinds back out through a containing
+    /// group/list/map, building up the breadcrumb path one level at a time.
+    fn within(mut self, label: &str) -> Self {
+        self.path.insert(0, label.to_string());
+        self
+    }
+}
+
+impl fmt::Display for TdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{} ", self.path.join("."))?;
+        }
+        write!(f, "(offset {}): ", self.offset)?;
+        match &self.kind {
+            TdfErrorKind::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TdfError {}
+
+impl From<TdfError> for io::Error {
+    fn from(err: TdfError) -> Self {
+        io::Error::new(ErrorKind::InvalidData, err.to_string())
+    }
+}
+
+/// Lifts an `io::Result` into a `TdfResult`, tagging it with `offset`, which callers
+/// take once at the start of the value being decoded.
+fn io_to_tdf<T>(result: io::Result<T>, offset: u64) -> TdfResult<T> {
+    result.map_err(|err| TdfError::new(TdfErrorKind::Io(err.to_string()), offset))
 }
 
 impl Tdf {
-    fn read<R: Read + Seek>(r: &mut R, tdf_type: &TdfType) -> io::Result<Self> {
+    fn read<R: Read + Seek>(r: &mut R, tdf_type: &TdfType) -> TdfResult<Self> {
+        let offset = io_to_tdf(r.stream_position(), 0)?;
         Ok(match tdf_type {
-            TdfType::VarInt => Tdf::VarInt(VarInt::read(r)?),
-            TdfType::String => Tdf::String(String::read(r)?),
+            TdfType::VarInt => Tdf::VarInt(io_to_tdf(VarInt::read(r), offset)?),
+            TdfType::String => Tdf::String(io_to_tdf(String::read(r), offset)?),
             TdfType::Blob => {
-                let size = VarInt::read(r)?.0 as usize;
+                let size = io_to_tdf(VarInt::read(r), offset)?.0 as usize;
                 let mut bytes = vec![0u8; size];
-                r.read_exact(&mut bytes)?;
+                io_to_tdf(r.read_exact(&mut bytes), offset)?;
                 Tdf::Blob(bytes)
             }
             TdfType::Group => {
                 let mut first_two = false;
                 let mut values: Vec<LabeledTdf> = Vec::new();
                 'group: loop {
-                    let first = r.read_u8()?;
+                    let first = io_to_tdf(r.read_u8(), offset)?;
                     if first == 0 {
                         break 'group;
                     } else if first == 2 {
                         first_two = true;
                     } else {
-                        r.seek(SeekFrom::Current(-1))?;
+                        io_to_tdf(r.seek(SeekFrom::Current(-1)), offset)?;
                     }
-                    values.push(LabeledTdf::read(r)?);
+                    values.push(LabeledTdf::read_tdf(r)?);
                 };
                 Tdf::Group(first_two, values)
             }
             TdfType::List => {
-                let sub_type = TdfType::from(r.read_u8()?);
-                let length = VarInt::read(r)?.0 as usize;
+                let sub_type = TdfType::from(io_to_tdf(r.read_u8(), offset)?);
+                let length = io_to_tdf(VarInt::read(r), offset)?.0 as usize;
                 let mut values = Vec::with_capacity(length);
-                for _ in 0..(length - 1) {
+                for _ in 0..length {
                     values.push(Tdf::read(r, &sub_type)?);
                 }
                 Tdf::List(sub_type, values)
             }
             TdfType::Map => {
-                let key_type = TdfType::from(r.read_u8()?);
-                let value_type = TdfType::from(r.read_u8()?);
-                let length = VarInt::read(r)?.0 as usize;
+                let key_type = TdfType::from(io_to_tdf(r.read_u8(), offset)?);
+                let value_type = TdfType::from(io_to_tdf(r.read_u8(), offset)?);
+                let length = io_to_tdf(VarInt::read(r), offset)?.0 as usize;
                 let mut keys = Vec::with_capacity(length);
                 let mut values = Vec::with_capacity(length);
-                for _ in 0..(length - 1) {
+                for _ in 0..length {
                     keys.push(Tdf::read(r, &key_type)?);
                     values.push(Tdf::read(r, &value_type)?);
                 }
                 Tdf::Map(key_type, value_type, keys, values)
             }
             TdfType::Union => {
-                let data = r.read_u8()?;
+                let data = io_to_tdf(r.read_u8(), offset)?;
                 let value = if data != 0x7F {
-                    Some(Box::new(LabeledTdf::read(r)?))
+                    Some(Box::new(LabeledTdf::read_tdf(r)?))
                 } else {
                     None
                 };
                 Tdf::Union(data, value)
             }
             TdfType::VarIntList => {
-                let length = VarInt::read(r)?.0 as usize;
+                let length = io_to_tdf(VarInt::read(r), offset)?.0 as usize;
                 let mut values = Vec::with_capacity(length);
-                for _ in 0..(length - 1) {
-                    values.push(VarInt::read(r)?);
+                for _ in 0..length {
+                    values.push(io_to_tdf(VarInt::read(r), offset)?);
                 }
                 Tdf::VarIntList(values)
             }
             TdfType::Pair => {
-                let a = VarInt::read(r)?;
-                let b = VarInt::read(r)?;
+                let a = io_to_tdf(VarInt::read(r), offset)?;
+                let b = io_to_tdf(VarInt::read(r), offset)?;
                 Tdf::Pair(a, b)
             }
             TdfType::Tripple => {
-                let a = VarInt::read(r)?;
-                let b = VarInt::read(r)?;
-                let c = VarInt::read(r)?;
+                let a = io_to_tdf(VarInt::read(r), offset)?;
+                let b = io_to_tdf(VarInt::read(r), offset)?;
+                let c = io_to_tdf(VarInt::read(r), offset)?;
                 Tdf::Tripple(a, b, c)
             }
             TdfType::Float => {
-                let value = r.read_f32::<BigEndian>()?;
+                let value = io_to_tdf(r.read_f32::<BigEndian>(), offset)?;
                 Tdf::Float(value)
             }
             TdfType::Unknown(_) => Tdf::Unknown
         })
     }
+}
 
-    fn get_text(&self, label: &str) -> TdfResult<String> {
-        if let Tdf::Group(_, values) = self {
-            for value in values {
-                if value.0 == label {
-                    if let Tdf::String(text) = &value.1 {
-                        Ok(text.clone())
-                    } else {
-                        Err(TdfError::InvalidType)
-                    }
-                }
-            }
-            Err(TdfError::MissingLabel)
-        } else {
-            Err(TdfError::NotGroup)
-        }
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn round_trip(value: i64) {
+        let mut buf = Vec::new();
+        VarInt(value).write(&mut buf).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let decoded = VarInt::read(&mut cursor).unwrap();
+        assert_eq!(decoded.0, value, "VarInt({value}) did not round-trip");
+    }
+
+    #[test]
+    fn varint_zigzag_round_trip() {
+        round_trip(0);
+        round_trip(-1);
+        round_trip(1);
+        round_trip(63);
+        round_trip(-64);
+        round_trip(64);
+        round_trip(-65);
+        round_trip(i64::MIN);
+        round_trip(i64::MAX);
     }
 }
@@ -0,0 +1,83 @@
+use std::io;
+
+use async_trait::async_trait;
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use crate::packet::{BlazePacketCodec, Packet};
+
+/// Abstracts the Blaze frame pipeline over whatever the client connected with, so the
+/// same packet handling drives both a raw TCP socket and a WebSocket tunnel.
+#[async_trait]
+pub trait Transport: Send {
+    /// Reads the next packet, or `Ok(None)` once the peer has disconnected cleanly.
+    async fn read_packet(&mut self) -> io::Result<Option<Packet>>;
+
+    async fn write_packet(&mut self, packet: Packet) -> io::Result<()>;
+}
+
+/// Raw TCP transport: a `Framed<TcpStream, BlazePacketCodec>` as a `Stream`/`Sink` of packets.
+pub struct TcpTransport {
+    framed: Framed<TcpStream, BlazePacketCodec>,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        TcpTransport { framed: Framed::new(stream, BlazePacketCodec) }
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        self.framed.next().await.transpose()
+    }
+
+    async fn write_packet(&mut self, packet: Packet) -> io::Result<()> {
+        self.framed.send(packet).await
+    }
+}
+
+/// WebSocket transport for clients behind networks that only allow outbound `ws://`/`wss://`
+/// traffic. Binary websocket message payloads are fed through the same `BlazePacketCodec`
+/// used for raw TCP, buffering across messages since a Blaze frame may span more than one.
+pub struct WsTransport<S> {
+    ws: WebSocketStream<S>,
+    codec: BlazePacketCodec,
+    buffer: BytesMut,
+}
+
+impl<S> WsTransport<S> {
+    pub fn new(ws: WebSocketStream<S>) -> Self {
+        WsTransport { ws, codec: BlazePacketCodec, buffer: BytesMut::new() }
+    }
+}
+
+#[async_trait]
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Transport for WsTransport<S> {
+    async fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            if let Some(packet) = self.codec.decode(&mut self.buffer)? {
+                return Ok(Some(packet));
+            }
+            match self.ws.next().await {
+                Some(Ok(Message::Binary(data))) => self.buffer.extend_from_slice(&data),
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => return Err(io::Error::other(err)),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    async fn write_packet(&mut self, packet: Packet) -> io::Result<()> {
+        let mut dst = BytesMut::new();
+        self.codec.encode(packet, &mut dst)?;
+        self.ws.send(Message::Binary(dst.to_vec())).await
+            .map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,94 @@
+//! Companion proc-macro crate for `rme3`: `#[derive(TdfSerialize, TdfDeserialize)]` maps a
+//! struct straight onto a `Tdf::Group` of `LabeledTdf` entries by 4-character tag, reusing
+//! `rme3`'s own `Readable`/`Writeable` and `FromTdf`/`IntoTdf` machinery so callers stop
+//! hand-assembling `LabeledTdf(String, TdfType, Tdf)` tuples.
+//!
+//! Generated code refers to `rme3` by crate name rather than `crate::`, so the derive also
+//! works when applied inside `rme3` itself (which re-exports itself via
+//! `extern crate self as rme3;`) as well as from any other crate depending on it.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(TdfSerialize, attributes(tdf))]
+pub fn derive_tdf_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = named_fields(&input.data, "TdfSerialize");
+
+    let entries = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().expect("named field");
+        let tag = field_tag(field);
+        let ty = &field.ty;
+        quote! {
+            rme3::tdf::LabeledTdf(
+                #tag.to_string(),
+                <#ty as rme3::tdf::IntoTdf>::tdf_type(),
+                rme3::tdf::IntoTdf::into_tdf(&self.#ident),
+            )
+        }
+    });
+
+    quote! {
+        impl rme3::tdf::IntoTdf for #name {
+            fn into_tdf(&self) -> rme3::tdf::Tdf {
+                rme3::tdf::Tdf::Group(false, vec![ #( #entries ),* ])
+            }
+
+            fn tdf_type() -> rme3::tdf::TdfType {
+                rme3::tdf::TdfType::Group
+            }
+        }
+    }.into()
+}
+
+#[proc_macro_derive(TdfDeserialize, attributes(tdf))]
+pub fn derive_tdf_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let fields = named_fields(&input.data, "TdfDeserialize");
+
+    let field_inits = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().expect("named field");
+        let tag = field_tag(field);
+        quote! {
+            #ident: rme3::tdf::find_field(values, #tag)?
+        }
+    });
+
+    quote! {
+        impl rme3::tdf::FromTdf for #name {
+            fn from_tdf(tdf: &rme3::tdf::Tdf) -> std::io::Result<Self> {
+                match tdf {
+                    rme3::tdf::Tdf::Group(_, values) => Ok(Self { #( #field_inits ),* }),
+                    _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "expected a group tdf value")),
+                }
+            }
+        }
+    }.into()
+}
+
+fn named_fields<'a>(data: &'a Data, derive_name: &str) -> &'a syn::punctuated::Punctuated<syn::Field, syn::token::Comma> {
+    match data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("{} only supports structs with named fields", derive_name),
+        },
+        _ => panic!("{} only supports structs", derive_name),
+    }
+}
+
+/// Reads the 4-character wire tag off a field's `#[tdf("TAG ")]` attribute.
+fn field_tag(field: &syn::Field) -> String {
+    for attr in &field.attrs {
+        if attr.path.is_ident("tdf") {
+            if let Ok(Meta::List(list)) = attr.parse_meta() {
+                if let Some(NestedMeta::Lit(Lit::Str(lit))) = list.nested.first() {
+                    return lit.value();
+                }
+            }
+        }
+    }
+    panic!("field `{}` is missing a #[tdf(\"TAG\")] attribute", field.ident.as_ref().unwrap());
+}